@@ -0,0 +1,221 @@
+// Copyright 2020 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::types::{Namespace, TypeName};
+
+/// A little C++ bit of code which we need to generate ourselves (as opposed to
+/// anything which cxx generates for us). Each [`AdditionalNeed`] is discovered
+/// during conversion and later handed to [`AdditionalCppGenerator`], which
+/// turns the whole list into a single header/implementation pair written out
+/// as `autocxxgen.h`/`.cc`.
+pub(crate) enum AdditionalNeed {
+    /// A typedef giving one of the variably-sized C types (e.g. `int`) the
+    /// name (e.g. `c_int`) that cxx refers to it by.
+    CTypeTypedef(TypeName),
+    /// A C++ shim for a polymorphic class: a concrete subclass which overrides
+    /// each listed method and forwards it, with `this`, to an `extern "C"`
+    /// trampoline named `autocxx_<class>_<method>`. The trampolines are
+    /// declared, not defined, by the generated C++.
+    AbstractClassShim {
+        ns: Namespace,
+        class: syn::Ident,
+        methods: Vec<syn::Ident>,
+    },
+    /// A non-inline C++ wrapper for a function bindgen cannot link against -
+    /// an inline or `static` member function, a defaulted constructor, etc. The
+    /// wrapper has a stable, mangling-free name and forwards to the original.
+    /// `params` are the wrapper's `(type, name)` parameters and `ret` its C++
+    /// return type (`void` if none).
+    FunctionThunk {
+        ns: Namespace,
+        thunk_name: syn::Ident,
+        cpp_name: String,
+        params: Vec<(String, String)>,
+        ret: String,
+    },
+}
+
+/// The pair of C++ artifacts we generate: the declarations destined for
+/// `autocxxgen.h` and the definitions destined for `autocxxgen.cc`.
+pub(crate) struct AdditionalCpp {
+    pub(crate) declarations: String,
+    pub(crate) definitions: String,
+}
+
+/// Accumulates [`AdditionalNeed`]s and renders the C++ we have to emit
+/// ourselves. Wrapper definitions are prefixed with the caller-supplied
+/// `cxx_impl_annotations` (e.g. `__attribute__((visibility("default")))`) so
+/// that autocxx output can be exported from a shared library.
+pub(crate) struct AdditionalCppGenerator {
+    inclusions: Vec<String>,
+    cxx_impl_annotations: Option<String>,
+    declarations: Vec<String>,
+    definitions: Vec<String>,
+}
+
+impl AdditionalCppGenerator {
+    pub(crate) fn new(inclusions: Vec<String>, cxx_impl_annotations: Option<String>) -> Self {
+        AdditionalCppGenerator {
+            inclusions,
+            cxx_impl_annotations,
+            declarations: Vec::new(),
+            definitions: Vec::new(),
+        }
+    }
+
+    /// The prefix applied to every generated wrapper definition, or the empty
+    /// string if the caller requested no annotation.
+    fn annotation_prefix(&self) -> String {
+        match &self.cxx_impl_annotations {
+            Some(a) => format!("{} ", a),
+            None => String::new(),
+        }
+    }
+
+    pub(crate) fn add_needs(&mut self, additions: Vec<AdditionalNeed>) {
+        for need in additions {
+            match need {
+                AdditionalNeed::CTypeTypedef(tn) => self.generate_ctype_typedef(&tn),
+                AdditionalNeed::AbstractClassShim { ns, class, methods } => {
+                    self.generate_abstract_class_shim(&ns, &class, &methods)
+                }
+                AdditionalNeed::FunctionThunk {
+                    ns,
+                    thunk_name,
+                    cpp_name,
+                    params,
+                    ret,
+                } => self.generate_function_thunk(&ns, &thunk_name, &cpp_name, &params, &ret),
+            }
+        }
+    }
+
+    fn generate_ctype_typedef(&mut self, tn: &TypeName) {
+        let cpp_name = tn.to_cpp_name();
+        self.declarations
+            .push(format!("typedef {} {};", cpp_name, tn.get_final_ident()));
+    }
+
+    fn generate_abstract_class_shim(
+        &mut self,
+        ns: &Namespace,
+        class: &syn::Ident,
+        methods: &[syn::Ident],
+    ) {
+        // Fully-qualified C++ name of the class, e.g. `a::b::Foo`, from which we
+        // also recover the namespace path so that the opaque instance type can
+        // be defined where cxx expects to find it.
+        let qualified = TypeName::new(ns, &class.to_string()).to_cpp_name();
+        let ns_path: Vec<&str> = qualified.split("::").collect();
+        let ns_path = &ns_path[..ns_path.len() - 1];
+        let shim = format!("autocxx_{}_shim", class);
+
+        // The trampolines the shim forwards into, one per method. They are
+        // declared here so the shim does not refer to undeclared symbols; their
+        // definitions are not generated by autocxx.
+        for m in methods {
+            self.declarations.push(format!(
+                "extern \"C\" void autocxx_{class}_{method}({qualified}* self);",
+                class = class,
+                method = m,
+                qualified = qualified,
+            ));
+        }
+
+        // A concrete subclass whose vtable entries trampoline back into Rust.
+        let overrides: Vec<String> = methods
+            .iter()
+            .map(|m| {
+                format!(
+                    "  void {method}() override {{ autocxx_{class}_{method}(this); }}",
+                    method = m,
+                    class = class,
+                )
+            })
+            .collect();
+        self.declarations.push(format!(
+            "class {annotations}{shim} : public {qualified} {{\npublic:\n{overrides}\n}};",
+            annotations = self.annotation_prefix(),
+            shim = shim,
+            qualified = qualified,
+            overrides = overrides.join("\n"),
+        ));
+
+        // The cxx-facing opaque instance type, defined in the class's namespace
+        // as an alias for the shim so the `type <Class>Instance;` in the bridge
+        // resolves to a real C++ type.
+        let open: String = ns_path.iter().map(|n| format!("namespace {} {{ ", n)).collect();
+        let close: String = std::iter::repeat("}").take(ns_path.len()).collect();
+        self.declarations.push(format!(
+            "{open}typedef ::{shim} {class}Instance; {close}",
+            open = open,
+            shim = shim,
+            class = class,
+            close = close,
+        ));
+    }
+
+    fn generate_function_thunk(
+        &mut self,
+        _ns: &Namespace,
+        thunk_name: &syn::Ident,
+        cpp_name: &str,
+        params: &[(String, String)],
+        ret: &str,
+    ) {
+        // A concrete, non-template wrapper so the linker actually sees a
+        // symbol for the stable name the cxx bridge calls. It forwards
+        // straight to the (fully-qualified) original.
+        let param_list: Vec<String> = params
+            .iter()
+            .map(|(ty, name)| format!("{} {}", ty, name))
+            .collect();
+        let arg_names: Vec<&str> = params.iter().map(|(_, name)| name.as_str()).collect();
+        let call = format!("{call}({args})", call = cpp_name, args = arg_names.join(", "));
+        let body = if ret == "void" {
+            format!("{};", call)
+        } else {
+            format!("return {};", call)
+        };
+        self.definitions.push(format!(
+            "{annotations}{ret} {thunk}({params}) {{ {body} }}",
+            annotations = self.annotation_prefix(),
+            ret = ret,
+            thunk = thunk_name,
+            params = param_list.join(", "),
+            body = body,
+        ));
+    }
+
+    /// Render the accumulated needs, or `None` if there was nothing to emit.
+    pub(crate) fn generate(&self) -> Option<AdditionalCpp> {
+        if self.declarations.is_empty() && self.definitions.is_empty() {
+            return None;
+        }
+        let includes: String = self
+            .inclusions
+            .iter()
+            .map(|inc| format!("#include \"{}\"\n", inc))
+            .collect();
+        let declarations = format!("{}{}", includes, self.declarations.join("\n"));
+        let definitions = format!(
+            "#include \"autocxxgen.h\"\n{}",
+            self.definitions.join("\n")
+        );
+        Some(AdditionalCpp {
+            declarations,
+            definitions,
+        })
+    }
+}
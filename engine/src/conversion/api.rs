@@ -14,13 +14,14 @@
 
 use crate::{
     additional_cpp_generator::AdditionalNeed,
-    types::{Namespace, TypeName},
+    types::{make_ident, Namespace, TypeName},
 };
+use quote::quote;
 use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
 };
-use syn::{ForeignItem, Ident, ImplItem, Item};
+use syn::{parse_quote, FnArg, ForeignItem, Ident, ImplItem, Item, Pat, ReturnType, Signature, Type};
 
 #[derive(Debug)]
 pub enum ConvertError {
@@ -56,22 +57,78 @@ impl Display for ConvertError {
 
 impl ConvertError {
     /// Whether we should ignore this error and simply skip over such items.
-    /// In the future we need to use this to provide diagnostics or logging to the user,
-    /// which ideally we'd somehow winkle into the generated bindings
-    /// in a way that causes them a compile-time problem only if they try to
-    /// _use_ the affects functions. I don't know a way to do that. Otherwise,
-    /// we should output these things as warnings during the codegen phase. TODO.
+    /// Rather than dropping the offending item silently, the parser keeps an
+    /// [`IgnoredApi`] around so that the codegen phase can winkle a poisoned
+    /// placeholder into the generated bindings: one which keeps the original
+    /// name and compiles fine, but which bites with a deprecation warning the
+    /// moment the user refers to it. We also emit the reasons as
+    /// `cargo:warning=` lines so that a vanished item is never a mystery.
     pub(crate) fn is_ignorable(&self) -> bool {
         matches!(self, ConvertError::VirtualThisType(_, _) | ConvertError::UnsupportedBuiltInType(_))
     }
+
+    /// Whether the dropped item was a function (as opposed to a type). Governs
+    /// the shape of the poisoned placeholder we emit: a panicking `fn` for a
+    /// function, a unit struct for a type.
+    pub(crate) fn is_function(&self) -> bool {
+        matches!(
+            self,
+            ConvertError::VirtualThisType(_, _)
+                | ConvertError::UnexpectedThisType(_, _)
+        )
+    }
+}
+
+/// An item which we failed to convert but whose error was
+/// [`ConvertError::is_ignorable`]. Instead of dropping it on the floor we
+/// carry it through to codegen, which emits a poisoned placeholder of the
+/// same name so the omission is visible to the user.
+pub(crate) struct IgnoredApi {
+    pub(crate) ns: Namespace,
+    pub(crate) id: Ident,
+    pub(crate) err: ConvertError,
+}
+
+/// An extensibility hook, inspired by bindgen's `ParseCallbacks`, which users
+/// register before code generation. The parser and codegen consult it while
+/// building and emitting each [`Api`], so that naming collisions can be
+/// resolved and types substituted without hand-patching generated output.
+pub(crate) trait ConversionCallbacks {
+    /// Rename an item within the given namespace, or return `None` to keep the
+    /// original name. Feeds [`Api::typename_for_allowlist`] and the `use`
+    /// statement's [`Use::UsedWithAlias`].
+    fn rename_item(&self, _ns: &Namespace, _original: &str) -> Option<String> {
+        None
+    }
+
+    /// Return `true` to drop an [`Api`] before codegen entirely.
+    fn blocklist(&self, _tn: &TypeName) -> bool {
+        false
+    }
+
+    /// Redirect a dependency to a hand-written or otherwise known type, or
+    /// return `None` to leave it untouched.
+    fn remap_type(&self, _tn: &TypeName) -> Option<TypeName> {
+        None
+    }
 }
 
+/// The default [`ConversionCallbacks`], used when the caller registers none.
+pub(crate) struct NoOpCallbacks;
+
+impl ConversionCallbacks for NoOpCallbacks {}
+
 /// Whetther and how this type should be exposed in the mods constructed
 /// for actual end-user use.
 pub(crate) enum Use {
     Unused,
     Used,
     UsedWithAlias(Ident),
+    /// A poisoned placeholder for an item we couldn't convert. Rather than a
+    /// `use` re-export, the facade mod for this item's namespace gets the stub
+    /// item itself, so it occupies the original name and trips its deprecation
+    /// warning on reference.
+    Poison(Box<Item>),
 }
 
 /// Any API we encounter in the input bindgen rs which we might want to pass
@@ -98,12 +155,184 @@ pub(crate) struct Api {
     pub(crate) impl_entry: Option<ImplItem>,
 }
 
+/// A single pure-virtual member function discovered on a C++ abstract or
+/// otherwise polymorphic class. The parser recognizes these from bindgen's
+/// generated `vtable`/virtual `this` and hands them here so that codegen can
+/// represent the class "as a void-ptr plus a jump table" - an opaque instance
+/// type plus a Rust trait whose methods dispatch through the C++ vtable.
+pub(crate) struct VirtualMethod {
+    pub(crate) id: Ident,
+    pub(crate) sig: Signature,
+}
+
+/// Best-effort rendering of a cxx-compatible Rust type as the C++ type it
+/// bridges to, used to give a forwarding thunk concrete parameter and return
+/// types (an `auto`/template wrapper would emit no linkable symbol). Primitives
+/// map to their `<cstdint>` spellings; pointers and references carry their
+/// const-ness across; any other path is assumed to share its final path
+/// segment with the C++ type, which holds for the class names cxx exposes.
+/// Types of any other shape render as `void`.
+fn cpp_type_of(ty: &Type) -> String {
+    match ty {
+        Type::Ptr(p) => {
+            let inner = cpp_type_of(&p.elem);
+            if p.const_token.is_some() {
+                format!("const {}*", inner)
+            } else {
+                format!("{}*", inner)
+            }
+        }
+        Type::Reference(r) => {
+            let inner = cpp_type_of(&r.elem);
+            if r.mutability.is_some() {
+                format!("{}&", inner)
+            } else {
+                format!("const {}&", inner)
+            }
+        }
+        Type::Path(p) => {
+            let last = p.path.segments.last().map(|s| s.ident.to_string());
+            match last.as_deref() {
+                Some("i8") => "int8_t".to_string(),
+                Some("i16") => "int16_t".to_string(),
+                Some("i32") => "int32_t".to_string(),
+                Some("i64") => "int64_t".to_string(),
+                Some("u8") => "uint8_t".to_string(),
+                Some("u16") => "uint16_t".to_string(),
+                Some("u32") => "uint32_t".to_string(),
+                Some("u64") => "uint64_t".to_string(),
+                Some("f32") => "float".to_string(),
+                Some("f64") => "double".to_string(),
+                Some("bool") => "bool".to_string(),
+                Some(other) => other.to_string(),
+                None => "void".to_string(),
+            }
+        }
+        _ => "void".to_string(),
+    }
+}
+
 impl Api {
+    /// Build the [`Api`] representing a C++ abstract/polymorphic class. This
+    /// turns the erstwhile [`ConvertError::VirtualThisType`] dead-end into real
+    /// support for calling C++ virtual methods by generating:
+    ///   * a Rust trait with one method per pure-virtual function, placed in
+    ///     `bindgen_mod_item` so it lands in the class's namespace mod;
+    ///   * a `cxxbridge`-compatible opaque type for instances; and
+    ///   * an [`AdditionalNeed`] that emits a C++ shim subclass whose vtable
+    ///     entries call out to `extern "C"` trampolines (see
+    ///     [`AdditionalNeed::AbstractClassShim`]). No Rust definitions of those
+    ///     trampolines are generated here.
+    pub(crate) fn abstract_class(ns: Namespace, id: Ident, methods: Vec<VirtualMethod>) -> Api {
+        let sigs = methods.iter().map(|m| &m.sig);
+        let trait_item: Item = parse_quote! {
+            pub trait #id {
+                #(#sigs;)*
+            }
+        };
+        // The opaque instance type, handed to cxx as an extern C++ type so it
+        // can be held behind a pointer and passed across the bridge. It needs a
+        // name distinct from the trait (which shares the class's name), so we
+        // suffix it.
+        let instance_id = make_ident(&format!("{}Instance", id));
+        let opaque: ForeignItem = ForeignItem::Verbatim(quote! {
+            type #instance_id;
+        });
+        let method_ids = methods.into_iter().map(|m| m.id).collect();
+        Api {
+            ns: ns.clone(),
+            id: instance_id,
+            use_stmt: Use::Used,
+            deps: HashSet::new(),
+            extern_c_mod_item: Some(opaque),
+            bridge_items: Vec::new(),
+            global_items: Vec::new(),
+            additional_cpp: Some(AdditionalNeed::AbstractClassShim {
+                ns,
+                class: id,
+                methods: method_ids,
+            }),
+            id_for_allowlist: None,
+            bindgen_mod_item: Some(trait_item),
+            impl_entry: None,
+        }
+    }
+
+    /// Build the [`Api`] for a function which bindgen could parse but cannot
+    /// link against - an inline or `static` member function, a defaulted
+    /// constructor, and so on, which has no external symbol. Mirroring
+    /// bindgen's `wrap-static-fns`, we synthesize a non-inline C++ wrapper with
+    /// a stable, mangling-free name that forwards to the original, emit it
+    /// through an [`AdditionalNeed`], and point the `extern "C++"` item at the
+    /// wrapper instead of the unlinkable original. Argument and return value
+    /// semantics are preserved via the cxx-compatible signature.
+    pub(crate) fn forwarding_thunk(
+        ns: Namespace,
+        id: Ident,
+        sig: Signature,
+        cpp_name: String,
+    ) -> Api {
+        let thunk_name = format!("autocxx_thunk_{}", id);
+        let thunk_id = make_ident(&thunk_name);
+        // The bridge declaration keeps the original Rust name but points cxx at
+        // our non-inline wrapper's symbol via `#[cxx_name]`, so callers are
+        // unaware the call is forwarded.
+        let mut sig = sig;
+        sig.ident = id.clone();
+        // Render the concrete C++ signature of the wrapper from the bridge
+        // signature, so the generated thunk is an ordinary function (with a
+        // real symbol) rather than an abbreviated template.
+        let params: Vec<(String, String)> = sig
+            .inputs
+            .iter()
+            .filter_map(|arg| match arg {
+                FnArg::Typed(pt) => {
+                    let name = match &*pt.pat {
+                        Pat::Ident(pi) => pi.ident.to_string(),
+                        _ => return None,
+                    };
+                    Some((cpp_type_of(&pt.ty), name))
+                }
+                FnArg::Receiver(_) => None,
+            })
+            .collect();
+        let ret = match &sig.output {
+            ReturnType::Type(_, ty) => cpp_type_of(ty),
+            ReturnType::Default => "void".to_string(),
+        };
+        let extern_item: ForeignItem = ForeignItem::Verbatim(quote! {
+            #[cxx_name = #thunk_name]
+            #sig;
+        });
+        Api {
+            ns: ns.clone(),
+            id,
+            use_stmt: Use::Used,
+            deps: HashSet::new(),
+            extern_c_mod_item: Some(extern_item),
+            bridge_items: Vec::new(),
+            global_items: Vec::new(),
+            additional_cpp: Some(AdditionalNeed::FunctionThunk {
+                ns,
+                thunk_name: thunk_id,
+                cpp_name,
+                params,
+                ret,
+            }),
+            id_for_allowlist: None,
+            bindgen_mod_item: None,
+            impl_entry: None,
+        }
+    }
+
     pub(crate) fn typename(&self) -> TypeName {
         TypeName::new(&self.ns, &self.id.to_string())
     }
 
     pub(crate) fn typename_for_allowlist(&self) -> TypeName {
+        // Any caller-requested rename has already been folded into
+        // `id_for_allowlist` and `use_stmt` in one place, when codegen applied
+        // the callbacks, so here we simply read it back.
         let id_for_allowlist = match &self.id_for_allowlist {
             None => match &self.use_stmt {
                 Use::UsedWithAlias(alias) => alias,
@@ -120,4 +349,8 @@ impl Api {
 pub(crate) struct ParseResults {
     pub(crate) apis: Vec<Api>,
     pub(crate) use_stmts_by_mod: HashMap<Namespace, Vec<Item>>,
+    /// Items which could not be converted but whose error was ignorable.
+    /// Codegen turns these into poisoned placeholders rather than letting
+    /// them vanish without trace.
+    pub(crate) ignored_apis: Vec<IgnoredApi>,
 }
@@ -24,7 +24,7 @@ use crate::{
 };
 
 use super::{
-    api::{Api, Use},
+    api::{Api, ConversionCallbacks, ConvertError, IgnoredApi, Use, VirtualMethod},
     namespace_organizer::NamespaceEntries,
 };
 
@@ -33,12 +33,207 @@ unzip_n::unzip_n!(pub 5);
 pub(crate) struct CodegenResults {
     pub(crate) items: Vec<Item>,
     pub(crate) additional_cpp_needs: Vec<AdditionalNeed>,
+    /// Annotation to prepend to every generated C++ wrapper definition, as
+    /// configured via [`CodegenOptions::cxx_impl_annotations`]. The C++
+    /// generator consumes this when emitting the `additional_cpp_needs`.
+    pub(crate) cxx_impl_annotations: Option<String>,
+    /// Human-readable reasons that one or more items were skipped. Codegen has
+    /// already printed each of these as a `cargo:warning=` line; they are kept
+    /// here so the caller can report them too.
+    pub(crate) warnings: Vec<String>,
+}
+
+/// Options controlling code generation, modelled on cxx's codegen `Opt`: a
+/// deliberately small, non-exhaustive surface that can grow over time without
+/// breaking callers. Construct with [`CodegenOptions::default`] and set only
+/// the fields you need.
+pub(crate) struct CodegenOptions {
+    /// A string such as `__attribute__((visibility("default")))` or
+    /// `__declspec(dllexport)` prepended to every generated C++ wrapper
+    /// definition. Essential when building autocxx output into a shared
+    /// library where the glue functions must be exported.
+    pub(crate) cxx_impl_annotations: Option<String>,
+    /// Extra `#include` directives to emit in the generated bridge beyond the
+    /// caller's `include_list`.
+    pub(crate) extra_includes: Vec<String>,
+    /// Whether to sort the generated items within each block by a stable key
+    /// so that trivial input changes don't reshuffle the emitted source. On by
+    /// default; turn off if you need items in raw `Api` iteration order.
+    pub(crate) sort_items: bool,
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        CodegenOptions {
+            cxx_impl_annotations: None,
+            extra_includes: Vec::new(),
+            sort_items: true,
+        }
+    }
 }
 
 fn remove_nones<T>(input: Vec<Option<T>>) -> Vec<T> {
     input.into_iter().flatten().collect()
 }
 
+/// Whether a bindgen-generated struct carries a vtable pointer, which is how
+/// bindgen represents a polymorphic C++ class (a field whose type mentions
+/// `_bindgen_vtable`).
+fn struct_has_vtable(s: &syn::ItemStruct) -> bool {
+    s.fields.iter().any(|f| {
+        let ty = &f.ty;
+        quote!(#ty).to_string().contains("_bindgen_vtable")
+    })
+}
+
+/// The first parameter of a foreign function, if it has one.
+fn first_param(f: &syn::ForeignItemFn) -> Option<&syn::PatType> {
+    f.sig.inputs.iter().next().and_then(|arg| match arg {
+        syn::FnArg::Typed(pt) => Some(pt),
+        syn::FnArg::Receiver(_) => None,
+    })
+}
+
+/// Whether this foreign function is a member function of `class`: its first
+/// parameter is the implicit `this`, whose pointee is `class`. That is how
+/// bindgen renders a C++ member function - a free function taking `this` as a
+/// leading `*const`/`*mut` argument.
+fn is_member_fn_of(f: &syn::ForeignItemFn, class: &syn::Ident) -> bool {
+    match first_param(f) {
+        Some(pt) => {
+            let is_this = matches!(&*pt.pat, syn::Pat::Ident(pi) if pi.ident == "this");
+            let points_at_class = match &*pt.ty {
+                syn::Type::Ptr(p) => match &*p.elem {
+                    syn::Type::Path(tp) => tp
+                        .path
+                        .segments
+                        .last()
+                        .map_or(false, |s| &s.ident == class),
+                    _ => false,
+                },
+                _ => false,
+            };
+            is_this && points_at_class
+        }
+        None => false,
+    }
+}
+
+/// Whether a foreign function lacks a linkable external symbol, so that a
+/// caller cannot be linked directly against it and needs a forwarding thunk.
+/// bindgen annotates a regular out-of-line function with a `#[link_name = ...]`
+/// carrying its mangled symbol; an inline, `static` or otherwise
+/// symbol-less member function is emitted without one. The absence of that
+/// attribute is therefore our signal.
+fn lacks_link_name(f: &syn::ForeignItemFn) -> bool {
+    !f.attrs.iter().any(|a| a.path.is_ident("link_name"))
+}
+
+/// Parser seam: inspect the freshly-parsed bindgen items and synthesize the
+/// extra [`Api`]s representing C++ features that cxx/bindgen can't express
+/// directly. Two cases are recognized from bindgen's own conventions:
+///   * a polymorphic class (a struct carrying a `_bindgen_vtable`), whose
+///     member functions become a Rust trait plus a vtable shim; and
+///   * a function bindgen emitted without a `#[link_name]`, which therefore has
+///     no linkable symbol and needs a forwarding thunk.
+/// The authoritative walk belongs in the bridge converter; this is the
+/// integration point codegen calls so the synthesized items flow through the
+/// same pipeline as everything else.
+fn synthesize_special_apis(all_apis: &mut Vec<Api>) {
+    synthesize_abstract_classes(all_apis);
+    synthesize_forwarding_thunks(all_apis);
+}
+
+/// Recognize polymorphic classes and turn each into a Rust trait plus a vtable
+/// shim, consuming the original struct so its identifier isn't defined twice.
+fn synthesize_abstract_classes(all_apis: &mut Vec<Api>) {
+    let polymorphic: Vec<(Namespace, syn::Ident)> = all_apis
+        .iter()
+        .filter_map(|api| match &api.bindgen_mod_item {
+            Some(Item::Struct(s)) if struct_has_vtable(s) => Some((api.ns.clone(), api.id.clone())),
+            _ => None,
+        })
+        .collect();
+    let mut extra = Vec::new();
+    for (ns, class) in &polymorphic {
+        // A member function of this class is a foreign fn in the same namespace
+        // whose leading `this` points at the class - that scopes collection to
+        // the one class rather than sweeping every `this`-taking fn in the
+        // namespace.
+        let methods: Vec<VirtualMethod> = all_apis
+            .iter()
+            .filter(|api| &api.ns == ns)
+            .filter_map(|api| match &api.extern_c_mod_item {
+                Some(ForeignItem::Fn(f)) if is_member_fn_of(f, class) => Some(VirtualMethod {
+                    id: api.id.clone(),
+                    sig: f.sig.clone(),
+                }),
+                _ => None,
+            })
+            .collect();
+        extra.push(Api::abstract_class(ns.clone(), class.clone(), methods));
+    }
+    // The polymorphic struct becomes a Rust trait of the same name, so drop the
+    // original struct Api to avoid a duplicate definition of that identifier.
+    all_apis.retain(|api| {
+        !polymorphic
+            .iter()
+            .any(|(ns, class)| &api.ns == ns && &api.id == class)
+    });
+    all_apis.append(&mut extra);
+}
+
+/// Replace each function that has no linkable symbol (inline, static member,
+/// defaulted) with a forwarding thunk, in place - so the bridge points at the
+/// thunk's stable symbol rather than emitting a second declaration alongside
+/// the original.
+fn synthesize_forwarding_thunks(all_apis: &mut Vec<Api>) {
+    for api in all_apis.iter_mut() {
+        let thunk = match &api.extern_c_mod_item {
+            Some(ForeignItem::Fn(f)) if lacks_link_name(f) => Some((
+                api.ns.clone(),
+                api.id.clone(),
+                f.sig.clone(),
+                api.typename().to_cpp_name(),
+            )),
+            _ => None,
+        };
+        if let Some((ns, id, sig, cpp_name)) = thunk {
+            *api = Api::forwarding_thunk(ns, id, sig, cpp_name);
+        }
+    }
+}
+
+/// Stable sort key - kind rank then name - for a top-level [`Item`], so that
+/// generated blocks come out in a reproducible order regardless of `Api`
+/// iteration order. Analogous to bindgen's `sort_semantically` postprocessing.
+fn item_sort_key(item: &Item) -> (u8, String) {
+    match item {
+        Item::Use(u) => (0, quote!(#u).to_string()),
+        Item::Type(t) => (1, t.ident.to_string()),
+        Item::Struct(s) => (2, s.ident.to_string()),
+        Item::Enum(e) => (3, e.ident.to_string()),
+        Item::Trait(t) => (4, t.ident.to_string()),
+        Item::Fn(f) => (5, f.sig.ident.to_string()),
+        Item::Impl(i) => (6, quote!(#i).to_string()),
+        Item::Mod(m) => (7, m.ident.to_string()),
+        other => (8, quote!(#other).to_string()),
+    }
+}
+
+/// Stable sort key - kind rank then name - for an item within an
+/// `extern "C++"` block. `include!` macros are deliberately ranked first so
+/// that they stay grouped at the top of the block.
+fn foreign_item_sort_key(item: &ForeignItem) -> (u8, String) {
+    match item {
+        ForeignItem::Macro(m) => (0, quote!(#m).to_string()),
+        ForeignItem::Type(t) => (1, t.ident.to_string()),
+        ForeignItem::Fn(f) => (2, f.sig.ident.to_string()),
+        ForeignItem::Static(s) => (3, s.ident.to_string()),
+        other => (4, quote!(#other).to_string()),
+    }
+}
+
 /// Type which handles generation of code.
 /// "Code" here includes a list of Items to expose in Rust,
 /// and also a list of "additional C++ needs" which can be passed
@@ -49,24 +244,111 @@ pub(crate) struct CodeGenerator<'a> {
     include_list: &'a [String],
     use_stmts_by_mod: HashMap<Namespace, Vec<Item>>,
     bindgen_mod: ItemMod,
+    options: CodegenOptions,
+    callbacks: &'a dyn ConversionCallbacks,
+    warnings: Vec<String>,
 }
 
 impl<'a> CodeGenerator<'a> {
     /// Generate code for a set of APIs that was discovered during parsing.
     pub(crate) fn generate_code(
-        all_apis: Vec<Api>,
+        mut all_apis: Vec<Api>,
+        ignored_apis: Vec<IgnoredApi>,
         include_list: &'a [String],
         use_stmts_by_mod: HashMap<Namespace, Vec<Item>>,
         bindgen_mod: ItemMod,
+        options: CodegenOptions,
+        callbacks: &'a dyn ConversionCallbacks,
     ) -> CodegenResults {
+        // Collect the reasons and print them as build warnings, and turn each
+        // skipped item into a poisoned placeholder so the omission bites at
+        // use time rather than vanishing silently.
+        let warnings: Vec<String> = ignored_apis
+            .iter()
+            .map(|i| {
+                format!(
+                    "autocxx could not generate bindings for {}{}: {}",
+                    i.id,
+                    i.ns.to_display_suffix(),
+                    i.err
+                )
+            })
+            .collect();
+        for warning in &warnings {
+            println!("cargo:warning={}", warning);
+        }
+        // Carry the poisoned placeholders alongside the real APIs so they flow
+        // through the same namespace facade - no parallel mod tree.
+        all_apis.extend(ignored_apis.into_iter().map(Self::poison_api));
+        // Recognize polymorphic classes and unlinkable functions, synthesizing
+        // the traits, shims and thunks that represent them.
+        synthesize_special_apis(&mut all_apis);
+        // Apply the user-registered hooks once, here, so they can't drift out
+        // of sync between the allowlist and the facade. `blocklist` drops an
+        // API; `rename_item` rewrites the exposed name (feeding both the
+        // allowlist id and the facade `use` alias). `remap_type` is applied
+        // later, against each API's dependencies.
+        all_apis.retain(|api| !callbacks.blocklist(&api.typename()));
+        for api in all_apis.iter_mut() {
+            if !matches!(api.use_stmt, Use::Used | Use::UsedWithAlias(_)) {
+                continue;
+            }
+            if let Some(new_name) = callbacks.rename_item(&api.ns, &api.id.to_string()) {
+                let alias = make_ident(&new_name);
+                api.id_for_allowlist.get_or_insert_with(|| alias.clone());
+                api.use_stmt = Use::UsedWithAlias(alias);
+            }
+        }
         let c = Self {
             include_list,
             use_stmts_by_mod,
             bindgen_mod,
+            options,
+            callbacks,
+            warnings,
         };
         c.codegen(all_apis)
     }
 
+    /// Build a placeholder [`Api`] for an item we couldn't convert. The
+    /// placeholder keeps the original name so that any reference to it resolves
+    /// but trips a `#[deprecated]` warning carrying the exact reason the real
+    /// item went missing. A dropped function becomes a panicking `fn` returning
+    /// `!` - so that actually calling it is a hard error at use time - whereas a
+    /// dropped type becomes a unit struct.
+    fn poison_api(ignored: IgnoredApi) -> Api {
+        let IgnoredApi { ns, id, err } = ignored;
+        let note = err.to_string();
+        let stub: Item = if err.is_function() {
+            parse_quote! {
+                #[deprecated(note = #note)]
+                pub fn #id() -> ! {
+                    panic!(#note)
+                }
+            }
+        } else {
+            parse_quote! {
+                #[deprecated(note = #note)]
+                pub struct #id;
+            }
+        };
+        Api {
+            ns,
+            id,
+            // The stub rides in the `Use` so it lands in the same facade mod as
+            // its siblings, rather than a separately-built parallel mod tree.
+            use_stmt: Use::Poison(Box::new(stub)),
+            deps: HashSet::new(),
+            extern_c_mod_item: None,
+            bridge_items: Vec::new(),
+            global_items: Vec::new(),
+            additional_cpp: None,
+            id_for_allowlist: None,
+            bindgen_mod_item: None,
+            impl_entry: None,
+        }
+    }
+
     fn codegen(mut self, all_apis: Vec<Api>) -> CodegenResults {
         // ... and now let's start to generate the output code.
         // First, the hierarchy of mods containing lots of 'use' statements
@@ -97,10 +379,26 @@ impl<'a> CodeGenerator<'a> {
         let mut all_items: Vec<Item> = all_items.into_iter().flatten().collect();
         // And finally any C++ we need to generate. And by "we" I mean autocxx not cxx.
         let mut additional_cpp_needs = remove_nones(additional_cpp_needs);
+        // Let callbacks redirect any dependency to a hand-written or known type
+        // before we reason about it further.
+        let deps: Vec<HashSet<TypeName>> = deps
+            .into_iter()
+            .map(|set| {
+                set.into_iter()
+                    .map(|tn| self.callbacks.remap_type(&tn).unwrap_or(tn))
+                    .collect()
+            })
+            .collect();
         // Determine what variably-sized C types (e.g. int) we need to include
         self.append_ctype_information(&deps, &mut extern_c_mod_items, &mut additional_cpp_needs);
         extern_c_mod_items
             .extend(self.build_include_foreign_items(!additional_cpp_needs.is_empty()));
+        // Normalize ordering so that trivial input changes don't reshuffle the
+        // emitted source, which would defeat diffing and caching.
+        if self.options.sort_items {
+            extern_c_mod_items.sort_by_key(foreign_item_sort_key);
+            bridge_items.sort_by_key(item_sort_key);
+        }
         // We will always create an extern "C" mod even if bindgen
         // didn't generate one, e.g. because it only generated types.
         // We still want cxx to know about those types.
@@ -131,6 +429,8 @@ impl<'a> CodeGenerator<'a> {
         CodegenResults {
             items: all_items,
             additional_cpp_needs,
+            cxx_impl_annotations: self.options.cxx_impl_annotations,
+            warnings: self.warnings,
         }
     }
 
@@ -169,8 +469,16 @@ impl<'a> CodeGenerator<'a> {
         } else {
             None
         };
-        let chained = self.include_list.iter().chain(extra_inclusion.iter());
+        let chained = self
+            .include_list
+            .iter()
+            .chain(self.options.extra_includes.iter())
+            .chain(extra_inclusion.iter());
+        // Merge redundant duplicate includes (e.g. the same header named in
+        // both `include_list` and `extra_includes`) while preserving order.
+        let mut seen = HashSet::new();
         chained
+            .filter(|inc| seen.insert((*inc).clone()))
             .map(|inc| {
                 ForeignItem::Macro(parse_quote! {
                     include!(#inc);
@@ -191,7 +499,12 @@ impl<'a> CodeGenerator<'a> {
     fn append_child_use_namespace(ns_entries: &NamespaceEntries, output_items: &mut Vec<Item>) {
         for item in ns_entries.entries() {
             let id = &item.id;
+            // Any caller-requested rename was folded into `use_stmt` when
+            // codegen applied the callbacks, so here we just honour it.
             match &item.use_stmt {
+                // A poisoned placeholder emits its stub item directly into this
+                // facade mod rather than a re-export.
+                Use::Poison(stub) => output_items.push((**stub).clone()),
                 Use::UsedWithAlias(alias) => output_items.push(Item::Use(parse_quote!(
                     pub use cxxbridge :: #id as #alias;
                 ))),
@@ -256,10 +569,16 @@ impl<'a> CodeGenerator<'a> {
                     }
                 );
                 self.append_uses_for_ns(&mut inner_output_items, &new_ns);
+                if self.options.sort_items {
+                    inner_output_items.sort_by_key(item_sort_key);
+                }
                 new_mod.content.as_mut().unwrap().1 = inner_output_items;
                 output_items.push(Item::Mod(new_mod));
             }
         }
+        if self.options.sort_items {
+            output_items.sort_by_key(item_sort_key);
+        }
     }
 
     fn generate_final_bindgen_mods(&mut self, input_items: &[Api]) -> Vec<Item> {
@@ -268,6 +587,12 @@ impl<'a> CodeGenerator<'a> {
         let ns_entries = NamespaceEntries::new(input_items);
         self.append_child_bindgen_namespace(&ns_entries, &mut output_items, &ns);
         self.append_uses_for_ns(&mut output_items, &ns);
+        // The root-level uses were appended after `append_child_bindgen_namespace`
+        // did its own sort, so re-sort here to fold them into the reproducible
+        // ordering rather than leaving them tacked on unsorted at the end.
+        if self.options.sort_items {
+            output_items.sort_by_key(item_sort_key);
+        }
         output_items
     }
 }